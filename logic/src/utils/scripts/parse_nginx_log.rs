@@ -2,20 +2,27 @@
 
 use std::collections::HashMap;
 use std::error::Error;
-
+use std::fmt::Write as _;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+use std::thread;
+use std::time::Duration;
+
+use bincode;
 use csv;
 use hyper::Method as HttpMethod;
 use rayon::prelude::*;
 use regex::{Regex, RegexBuilder};
 
 use serde;
-use serde::ser::SerializeMap;
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
 // CONSTANTS
 
-// Skips any requests with more than this number of resources in path
-const MAX_PATH_DEPTH: usize = 15;
+// Default limit on the number of resources in a path; callers can override
+// this via `parse_nginx_log_with_options`
+pub const DEFAULT_MAX_PATH_DEPTH: usize = 15;
 
 // Estimated sizes for allocation
 const ROOT_PATHS_INIT_SIZE: usize = 25;
@@ -30,6 +37,12 @@ const INT_PARAM: &str = "<INT>";
 const UUID_PARAM: &str = "<UUID>";
 const CONTAINS_DIGIT_PARAM: &str = "<CONTAINS_DIGIT>";
 
+// Latency histogram: number of log-linear buckets covering ~0.1ms to 80s,
+// each roughly 16.5% wider than the previous (same units as `request_time`)
+const HISTOGRAM_BUCKET_COUNT: usize = 90;
+const HISTOGRAM_MIN_BOUNDARY: f64 = 0.0001;
+const HISTOGRAM_GROWTH_FACTOR: f64 = 1.165;
+
 #[derive(Debug)]
 pub struct ServerStatistic {
     /// Request timestamp in format "YYYY-MM-DD HH:MM:SS"
@@ -50,10 +63,54 @@ pub struct ServerStatistic {
     pub max_duration: f32,
     // Total number of errors
     pub errors: u32,
+    /// 50th percentile duration in seconds (histogram bucket upper boundary)
+    pub p50: f32,
+    /// 90th percentile duration in seconds (histogram bucket upper boundary)
+    pub p90: f32,
+    /// 95th percentile duration in seconds (histogram bucket upper boundary)
+    pub p95: f32,
+    /// 99th percentile duration in seconds (histogram bucket upper boundary)
+    pub p99: f32,
+}
+
+lazy_static! {
+    /// Upper boundary of each latency histogram bucket
+    static ref HISTOGRAM_BOUNDARIES: [f32; HISTOGRAM_BUCKET_COUNT] = {
+        let mut boundaries = [0.0f32; HISTOGRAM_BUCKET_COUNT];
+        let mut boundary = HISTOGRAM_MIN_BOUNDARY;
+
+        for b in boundaries.iter_mut() {
+            *b = boundary as f32;
+            boundary *= HISTOGRAM_GROWTH_FACTOR;
+        }
+
+        boundaries
+    };
+}
+
+/// Finds the histogram bucket a duration falls into, clamping values above
+/// the top boundary into the last bucket
+fn histogram_bucket(duration: f32) -> usize {
+    HISTOGRAM_BOUNDARIES
+        .partition_point(|b| *b <= duration)
+        .min(HISTOGRAM_BUCKET_COUNT - 1)
 }
 
 // Parse into HashMap, then create tree
 pub fn parse_nginx_log(path: &str) -> Result<Vec<ServerStatistic>, Box<dyn Error>> {
+    parse_nginx_log_with_options(path, &[], DEFAULT_MAX_PATH_DEPTH)
+}
+
+/// Like `parse_nginx_log`, but lets the caller override path-segment
+/// normalization with an ordered list of `(pattern, replacement)` rules
+/// (first match wins per segment, falling back to the built-in
+/// `<INT>`/`<UUID>`/`<CONTAINS_DIGIT>` classification when `rules` is empty)
+/// and the maximum path depth retained in the hierarchy
+pub fn parse_nginx_log_with_options(
+    path: &str,
+    rules: &[(Regex, String)],
+    max_path_depth: usize,
+) -> Result<Vec<ServerStatistic>, Box<dyn Error>> {
     let mut results = ParseNginxLog::default();
     let mut request_count: u32 = 0;
 
@@ -65,7 +122,8 @@ pub fn parse_nginx_log(path: &str) -> Result<Vec<ServerStatistic>, Box<dyn Error
     let mut raw_record = csv::StringRecord::new();
     while csv_reader.read_record(&mut raw_record)? {
         match raw_record.deserialize(None) {
-            Ok(request) => {
+            Ok(mut request) => {
+                normalize_request_path(&mut request, rules);
                 results.insert(request);
                 request_count += 1;
             }
@@ -75,7 +133,97 @@ pub fn parse_nginx_log(path: &str) -> Result<Vec<ServerStatistic>, Box<dyn Error
 
     println!("Processed {} requests", request_count);
 
-    results.build_hierarchies();
+    results.build_hierarchies(max_path_depth);
+
+    let final_results = results.flatten();
+    println!("Produced {} final result entries", final_results.len());
+
+    Ok(final_results)
+}
+
+/// Parses `path` and renders the aggregated results in the Prometheus /
+/// OpenMetrics text exposition format, ready to be scraped or pushed to a TSDB
+pub fn parse_nginx_log_prometheus(path: &str) -> Result<String, Box<dyn Error>> {
+    parse_nginx_log_prometheus_with_options(path, &[], DEFAULT_MAX_PATH_DEPTH)
+}
+
+/// Like `parse_nginx_log_prometheus`, but lets the caller override
+/// path-segment normalization with `rules` and the maximum path depth
+/// retained in the hierarchy, same as `parse_nginx_log_with_options`
+pub fn parse_nginx_log_prometheus_with_options(
+    path: &str,
+    rules: &[(Regex, String)],
+    max_path_depth: usize,
+) -> Result<String, Box<dyn Error>> {
+    let statistics = parse_nginx_log_with_options(path, rules, max_path_depth)?;
+    Ok(to_prometheus(&statistics))
+}
+
+/// Incrementally parses `path`, resuming from the byte offset and per-hour
+/// aggregation saved in `state_path` (if any), and folds only the newly
+/// appended records into it before building hierarchies and flattening
+pub fn parse_nginx_log_resume(
+    path: &str,
+    state_path: &str,
+) -> Result<Vec<ServerStatistic>, Box<dyn Error>> {
+    parse_nginx_log_resume_with_options(path, state_path, &[], DEFAULT_MAX_PATH_DEPTH)
+}
+
+/// Like `parse_nginx_log_resume`, but lets the caller override path-segment
+/// normalization with `rules` and the maximum path depth retained in the
+/// hierarchy, same as `parse_nginx_log_with_options`
+pub fn parse_nginx_log_resume_with_options(
+    path: &str,
+    state_path: &str,
+    rules: &[(Regex, String)],
+    max_path_depth: usize,
+) -> Result<Vec<ServerStatistic>, Box<dyn Error>> {
+    let mut checkpoint = ParseNginxLogCheckpoint::load(state_path)?;
+    let seek_offset = checkpoint.offset;
+
+    let mut file = File::open(path)?;
+    let file_len = file.metadata()?.len();
+    let ends_with_newline = file_ends_with_newline(path, file_len)?;
+    file.seek(SeekFrom::Start(seek_offset))?;
+
+    let mut csv_reader = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .delimiter(LOG_DELIMITER as u8)
+        .from_reader(file);
+
+    let mut raw_record = csv::StringRecord::new();
+    let mut request_count: u32 = 0;
+
+    while csv_reader.read_record(&mut raw_record)? {
+        let new_offset = seek_offset + csv_reader.position().byte();
+
+        // the csv crate still returns a "complete" record for a trailing
+        // line with no terminating newline, which is exactly the
+        // in-progress-write case this function guards against; leave it
+        // (and the checkpoint offset) alone so it's re-read once the write
+        // finishes and the newline lands
+        if new_offset == file_len && !ends_with_newline {
+            break;
+        }
+
+        checkpoint.offset = new_offset;
+
+        match raw_record.deserialize(None) {
+            Ok(mut request) => {
+                normalize_request_path(&mut request, rules);
+                checkpoint.log.insert(request);
+                request_count += 1;
+            }
+            Err(e) => println!("{:?}", e),
+        }
+    }
+
+    println!("Processed {} new requests", request_count);
+
+    checkpoint.save(state_path)?;
+
+    let mut results = checkpoint.log;
+    results.build_hierarchies(max_path_depth);
 
     let final_results = results.flatten();
     println!("Produced {} final result entries", final_results.len());
@@ -83,6 +231,206 @@ pub fn parse_nginx_log(path: &str) -> Result<Vec<ServerStatistic>, Box<dyn Error
     Ok(final_results)
 }
 
+/// Whether the file at `path` (of length `file_len`) ends in a newline,
+/// i.e. whether its last line is complete
+fn file_ends_with_newline(path: &str, file_len: u64) -> Result<bool, Box<dyn Error>> {
+    if file_len == 0 {
+        return Ok(true);
+    }
+
+    let mut file = File::open(path)?;
+    file.seek(SeekFrom::End(-1))?;
+
+    let mut last_byte = [0u8; 1];
+    file.read_exact(&mut last_byte)?;
+
+    Ok(last_byte[0] == b'\n')
+}
+
+/// Persisted checkpoint: the byte offset reached in the source file, and the
+/// pre-hierarchy `full_paths` aggregation (captured before `build_hierarchies`
+/// consumes it), so appended lines can be folded in on the next resume
+#[derive(Default, Debug, Serialize, Deserialize)]
+struct ParseNginxLogCheckpoint {
+    offset: u64,
+    log: ParseNginxLog,
+}
+
+impl ParseNginxLogCheckpoint {
+    fn load(state_path: &str) -> Result<Self, Box<dyn Error>> {
+        if !Path::new(state_path).exists() {
+            return Ok(ParseNginxLogCheckpoint::default());
+        }
+
+        let file = File::open(state_path)?;
+        Ok(bincode::deserialize_from(file)?)
+    }
+
+    fn save(&self, state_path: &str) -> Result<(), Box<dyn Error>> {
+        let file = File::create(state_path)?;
+        bincode::serialize_into(file, self)?;
+
+        Ok(())
+    }
+}
+
+/// Default interval between polls for newly appended bytes while following
+pub const DEFAULT_POLL_INTERVAL_MS: u64 = 500;
+
+/// Streams a log hour bucket at a time, sealing (building the hierarchy for
+/// and flattening) the current hour as soon as a strictly later hour is
+/// observed, instead of buffering the whole file before producing results.
+/// In follow mode the file handle is kept open and polled for newly appended
+/// bytes instead of stopping at EOF.
+pub struct NginxLogFollower {
+    csv_reader: csv::Reader<File>,
+    raw_record: csv::StringRecord,
+    current_hour_key: Option<String>,
+    current_hour: ParseNginxLogHour,
+    follow: bool,
+    poll_interval: Duration,
+    rules: Vec<(Regex, String)>,
+    max_path_depth: usize,
+    done: bool,
+}
+
+impl NginxLogFollower {
+    pub fn new(path: &str, follow: bool) -> Result<Self, Box<dyn Error>> {
+        Self::with_options(
+            path,
+            follow,
+            Duration::from_millis(DEFAULT_POLL_INTERVAL_MS),
+            &[],
+            DEFAULT_MAX_PATH_DEPTH,
+        )
+    }
+
+    pub fn with_poll_interval(
+        path: &str,
+        follow: bool,
+        poll_interval: Duration,
+    ) -> Result<Self, Box<dyn Error>> {
+        Self::with_options(path, follow, poll_interval, &[], DEFAULT_MAX_PATH_DEPTH)
+    }
+
+    /// Like `new`, but also lets the caller override the path-segment
+    /// normalization rules and the maximum path depth, same as
+    /// `parse_nginx_log_with_options`
+    pub fn with_options(
+        path: &str,
+        follow: bool,
+        poll_interval: Duration,
+        rules: &[(Regex, String)],
+        max_path_depth: usize,
+    ) -> Result<Self, Box<dyn Error>> {
+        let csv_reader = csv::ReaderBuilder::new()
+            .has_headers(false)
+            .delimiter(LOG_DELIMITER as u8)
+            .from_path(path)?;
+
+        Ok(NginxLogFollower {
+            csv_reader,
+            raw_record: csv::StringRecord::new(),
+            current_hour_key: None,
+            current_hour: ParseNginxLogHour::default(),
+            follow,
+            poll_interval,
+            rules: rules.to_vec(),
+            max_path_depth,
+            done: false,
+        })
+    }
+
+    /// Returns the statistics for the next sealed hour bucket, polling (in
+    /// follow mode) while waiting for it to seal, or `None` once the file is
+    /// exhausted and no hour is left pending (non-follow mode only)
+    pub fn next_sealed_hour(&mut self) -> Result<Option<Vec<ServerStatistic>>, Box<dyn Error>> {
+        if self.done {
+            return Ok(None);
+        }
+
+        loop {
+            if !self.csv_reader.read_record(&mut self.raw_record)? {
+                if self.follow {
+                    thread::sleep(self.poll_interval);
+
+                    // the csv reader latches EOF once hit; re-seek to where it
+                    // stopped so bytes appended since then are read
+                    let position = self.csv_reader.position().clone();
+                    self.csv_reader
+                        .seek_raw(SeekFrom::Start(position.byte()), position)?;
+                    continue;
+                }
+
+                self.done = true;
+                return Ok(seal_hour(
+                    &mut self.current_hour_key,
+                    &mut self.current_hour,
+                    self.max_path_depth,
+                ));
+            }
+
+            let mut request = match self.raw_record.deserialize(None) {
+                Ok(request) => request,
+                Err(e) => {
+                    println!("{:?}", e);
+                    continue;
+                }
+            };
+            normalize_request_path(&mut request, &self.rules);
+
+            let is_newer_hour = match &self.current_hour_key {
+                Some(hour) => does_request_seal_hour(&request, hour),
+                None => false,
+            };
+
+            if is_newer_hour {
+                let sealed = seal_hour(
+                    &mut self.current_hour_key,
+                    &mut self.current_hour,
+                    self.max_path_depth,
+                );
+                self.current_hour_key = Some(request.time_iso8601.clone());
+                self.current_hour.insert_request(request);
+
+                return Ok(sealed);
+            }
+
+            if self.current_hour_key.is_none() {
+                self.current_hour_key = Some(request.time_iso8601.clone());
+            }
+            self.current_hour.insert_request(request);
+        }
+    }
+}
+
+/// Whether `request` belongs to a strictly later hour than `current_hour`,
+/// meaning `current_hour` can be sealed and flushed
+fn does_request_seal_hour(request: &Request<'_>, current_hour: &str) -> bool {
+    request.time_iso8601.as_str() > current_hour
+}
+
+/// Builds the hierarchy for and flattens the current hour, if any, then
+/// resets it so the next hour can start accumulating from scratch
+fn seal_hour(
+    current_hour_key: &mut Option<String>,
+    current_hour: &mut ParseNginxLogHour,
+    max_path_depth: usize,
+) -> Option<Vec<ServerStatistic>> {
+    let key = current_hour_key.take()?;
+    let mut hour = std::mem::take(current_hour);
+
+    hour.build_hierarchy(&key, max_path_depth);
+    hour.flatten();
+
+    Some(hour.flat)
+}
+
+/// Normalizes a deserialized request's path in place using `normalize_path`
+fn normalize_request_path(request: &mut Request<'_>, rules: &[(Regex, String)]) {
+    request.request.path = normalize_path(&request.request.path, rules);
+}
+
 /// Struct that NGINX logs get deserialed into
 #[derive(Debug, Deserialize)]
 struct Request<'a> {
@@ -117,7 +465,7 @@ struct RequestPath {
 }
 
 /// Top level results struct
-#[derive(Default, Debug)]
+#[derive(Default, Debug, Serialize, Deserialize)]
 struct ParseNginxLog {
     hours: HashMap<String, ParseNginxLogHour>,
 }
@@ -127,17 +475,13 @@ impl ParseNginxLog {
         self.hours
             .entry(request.time_iso8601.to_string())
             .or_insert_with(ParseNginxLogHour::default)
-            .full_paths
-            .get_or_insert(HashMap::new())
-            .entry(request.request.path.to_string())
-            .and_modify(|e| e.increment(&request))
-            .or_insert_with(|| RequestNode::default_with_request(request));
+            .insert_request(request);
     }
 
-    fn build_hierarchies(&mut self) {
+    fn build_hierarchies(&mut self, max_path_depth: usize) {
         self.hours
             .par_iter_mut()
-            .for_each(|(k, v)| v.build_hierarchy(k));
+            .for_each(|(k, v)| v.build_hierarchy(k, max_path_depth));
     }
 
     fn flatten(&mut self) -> Vec<ServerStatistic> {
@@ -154,10 +498,14 @@ impl ParseNginxLog {
 }
 
 /// Results for a specific hour
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 struct ParseNginxLogHour {
     full_paths: Option<HashMap<String, RequestNode>>,
+    // Derived from `full_paths` by `build_hierarchy`/`flatten`, so checkpoints
+    // only need to persist the pre-hierarchy aggregation
+    #[serde(skip)]
     hierarchy: Option<RequestNode>,
+    #[serde(skip)]
     flat: Vec<ServerStatistic>,
 }
 
@@ -172,8 +520,17 @@ impl Default for ParseNginxLogHour {
 }
 
 impl ParseNginxLogHour {
+    /// Folds a single request into this hour's pre-hierarchy `full_paths` map
+    fn insert_request(&mut self, request: Request<'_>) {
+        self.full_paths
+            .get_or_insert(HashMap::new())
+            .entry(request.request.path.to_string())
+            .and_modify(|e| e.increment(&request))
+            .or_insert_with(|| RequestNode::default_with_request(request));
+    }
+
     /// Builds RequestNode structure from full paths
-    fn build_hierarchy(&mut self, time: &str) {
+    fn build_hierarchy(&mut self, time: &str, max_path_depth: usize) {
         self.hierarchy = Some(RequestNode::default());
 
         if let Some(hierarchy) = &mut self.hierarchy {
@@ -181,7 +538,7 @@ impl ParseNginxLogHour {
 
             if let Some(full_paths) = &self.full_paths {
                 for request in full_paths.values() {
-                    hierarchy.insert(request);
+                    hierarchy.insert(request, max_path_depth);
                 }
             }
         }
@@ -203,11 +560,14 @@ impl ParseNginxLogHour {
 }
 
 /// All metrics for a specific path
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 struct RequestNode {
     total: Metrics,
 
-    #[serde(serialize_with = "serialize_methods")]
+    #[serde(
+        serialize_with = "serialize_methods",
+        deserialize_with = "deserialize_methods"
+    )]
     http_methods: Option<HashMap<HttpMethod, Metrics>>,
 
     access_timestamp: String,
@@ -224,19 +584,41 @@ where
 {
     match http_methods {
         Some(methods) => {
-            let mut map = serializer.serialize_map(Some(methods.len()))?;
-            for (k, v) in methods {
-                map.serialize_entry(&k.to_string(), v)?;
-            }
+            let by_name: HashMap<String, &Metrics> =
+                methods.iter().map(|(k, v)| (k.to_string(), v)).collect();
 
-            map.end()
+            serializer.serialize_some(&by_name)
         }
         None => serializer.serialize_none(),
     }
 }
 
+fn deserialize_methods<'de, D>(
+    deserializer: D,
+) -> Result<Option<HashMap<HttpMethod, Metrics>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let by_name: Option<HashMap<String, Metrics>> = Option::deserialize(deserializer)?;
+
+    match by_name {
+        Some(methods) => {
+            let mut http_methods = HashMap::with_capacity(methods.len());
+            for (method, metrics) in methods {
+                let method = method
+                    .parse::<HttpMethod>()
+                    .map_err(serde::de::Error::custom)?;
+                http_methods.insert(method, metrics);
+            }
+
+            Ok(Some(http_methods))
+        }
+        None => Ok(None),
+    }
+}
+
 /// Multiuse metrics struct
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 struct Metrics {
     count: u32,
     download: u32, // In bytes
@@ -244,6 +626,11 @@ struct Metrics {
     min_duration: Option<f32>,
     max_duration: Option<f32>,
     errors: u32,
+    /// Log-linear latency histogram, one count per bucket in `HISTOGRAM_BOUNDARIES`.
+    /// A `Vec` rather than a `[u32; HISTOGRAM_BUCKET_COUNT]` array, since
+    /// `derive(Deserialize)` only covers fixed-size arrays up to 32 elements
+    /// and `ParseNginxLogCheckpoint` round-trips this struct through bincode
+    buckets: Vec<u32>,
 }
 
 impl Default for Metrics {
@@ -255,6 +642,7 @@ impl Default for Metrics {
             min_duration: None,
             max_duration: None,
             errors: 0,
+            buckets: vec![0; HISTOGRAM_BUCKET_COUNT],
         }
     }
 }
@@ -289,6 +677,7 @@ impl Metrics {
         }
 
         self.duration += request.request_time;
+        self.buckets[histogram_bucket(request.request_time)] += 1;
 
         if request.status >= 500 {
             self.errors += 1;
@@ -308,6 +697,31 @@ impl Metrics {
         self.duration += metrics.duration;
 
         self.errors += metrics.errors;
+
+        for (bucket, other_bucket) in self.buckets.iter_mut().zip(metrics.buckets.iter()) {
+            *bucket += other_bucket;
+        }
+    }
+
+    /// Returns the upper boundary of the bucket containing the `q`th quantile
+    /// (e.g. `q = 0.99` for p99), walking cumulative bucket counts until the
+    /// target rank is reached
+    fn quantile(&self, q: f64) -> f32 {
+        if self.count == 0 {
+            return 0.0;
+        }
+
+        let target = ((q * self.count as f64).ceil() as u32).max(1);
+        let mut cumulative: u32 = 0;
+
+        for (bucket, boundary) in self.buckets.iter().zip(HISTOGRAM_BOUNDARIES.iter()) {
+            cumulative += bucket;
+            if cumulative >= target {
+                return *boundary;
+            }
+        }
+
+        *HISTOGRAM_BOUNDARIES.last().unwrap()
     }
 }
 
@@ -336,13 +750,18 @@ impl RequestNode {
         methods.insert(request.request.http_method, method_metrics);
 
         RequestNode {
-            total: Metrics {
-                count: 1,
-                download: request.bytes_sent,
-                min_duration: Some(request.request_time),
-                max_duration: Some(request.request_time),
-                duration: request.request_time,
-                errors,
+            total: {
+                let mut total = Metrics {
+                    count: 1,
+                    download: request.bytes_sent,
+                    min_duration: Some(request.request_time),
+                    max_duration: Some(request.request_time),
+                    duration: request.request_time,
+                    errors,
+                    buckets: vec![0; HISTOGRAM_BUCKET_COUNT],
+                };
+                total.buckets[histogram_bucket(request.request_time)] = 1;
+                total
             },
             http_methods: Some(methods),
 
@@ -352,28 +771,28 @@ impl RequestNode {
         }
     }
 
-    fn insert(&mut self, request: &RequestNode) {
+    fn insert(&mut self, request: &RequestNode, max_path_depth: usize) {
         let resources: Vec<_> = request.path.split(RESOURCE_DELIMITER).collect();
+        let depth = resources.len();
 
-        match resources.len() {
-            0..=1 => return,
-            2 => {
-                self.increment_node(request);
-
-                if let Some(methods) = &request.http_methods {
-                    for (method, metrics) in methods {
-                        self.http_methods
-                            .get_or_insert_with(HashMap::new)
-                            .entry(method.clone())
-                            .and_modify(|e| e.increment_with_metric(metrics))
-                            .or_insert_with(|| metrics.clone());
-                    }
-                }
+        if depth <= 1 || depth > max_path_depth {
+            return;
+        }
 
-                return;
+        if depth == 2 {
+            self.increment_node(request);
+
+            if let Some(methods) = &request.http_methods {
+                for (method, metrics) in methods {
+                    self.http_methods
+                        .get_or_insert_with(HashMap::new)
+                        .entry(method.clone())
+                        .and_modify(|e| e.increment_with_metric(metrics))
+                        .or_insert_with(|| metrics.clone());
+                }
             }
-            3..=MAX_PATH_DEPTH => {}
-            _ => return,
+
+            return;
         }
 
         self.increment_node(request);
@@ -436,6 +855,7 @@ impl RequestNode {
         }
 
         self.total.duration += request.request_time;
+        self.total.buckets[histogram_bucket(request.request_time)] += 1;
 
         if request.status >= 500 {
             self.total.errors += 1;
@@ -462,6 +882,15 @@ impl RequestNode {
 
         self.total.duration += request_node.total.duration;
         self.total.errors += request_node.total.errors;
+
+        for (bucket, other_bucket) in self
+            .total
+            .buckets
+            .iter_mut()
+            .zip(request_node.total.buckets.iter())
+        {
+            *bucket += other_bucket;
+        }
     }
 
     fn flatten(&self, vec: &mut Vec<ServerStatistic>) {
@@ -478,6 +907,10 @@ impl RequestNode {
                 min_duration,
                 max_duration,
                 errors: self.total.errors,
+                p50: self.total.quantile(0.50),
+                p90: self.total.quantile(0.90),
+                p95: self.total.quantile(0.95),
+                p99: self.total.quantile(0.99),
             });
         }
 
@@ -496,6 +929,10 @@ impl RequestNode {
                         min_duration,
                         max_duration,
                         errors: metrics.errors,
+                        p50: metrics.quantile(0.50),
+                        p90: metrics.quantile(0.90),
+                        p95: metrics.quantile(0.95),
+                        p99: metrics.quantile(0.99),
                     });
                 }
             }
@@ -521,7 +958,9 @@ where
     }
 }
 
-/// Deserialize request path
+/// Deserialize request path. Path-segment normalization (`<INT>`/`<UUID>`/
+/// custom rules) happens afterwards, via `normalize_request_path`, since it
+/// depends on caller-supplied rules that aren't available to `serde`
 fn deserialize_request_path<'de, D>(deserializer: D) -> Result<RequestPath, D::Error>
 where
     D: Deserializer<'de>,
@@ -530,6 +969,55 @@ where
         static ref REQUEST: Regex =
             Regex::new(r#"^(GET|HEAD|POST|PUT|DELETE|CONNECT|OPTIONS|TRACE|PATCH) (/[^\s\?#]*)"#)
                 .unwrap();
+    }
+
+    let s: &str = Deserialize::deserialize(deserializer)?;
+    if let Some(captures) = REQUEST.captures(s) {
+        if captures.len() < 3 {
+            return Ok(RequestPath::default());
+        }
+
+        if let Ok(http_method) = captures[1].parse::<HttpMethod>() {
+            return Ok(RequestPath {
+                http_method,
+                path: captures[2].to_string(),
+            });
+        }
+    }
+
+    Ok(RequestPath::default())
+}
+
+/// Normalizes each `/`-delimited segment of `path` in turn: if `rules` is
+/// non-empty, the first `(pattern, replacement)` rule whose pattern matches
+/// the segment wins (a segment matching no rule is left as-is); otherwise
+/// falls back to the built-in `<INT>`/`<UUID>`/`<CONTAINS_DIGIT>`
+/// classification
+fn normalize_path(path: &str, rules: &[(Regex, String)]) -> String {
+    let mut resources_new: Vec<String> = path
+        .split(RESOURCE_DELIMITER)
+        .map(|resource| classify_resource(resource, rules))
+        .collect();
+
+    if resources_new[resources_new.len() - 1].is_empty() {
+        resources_new.remove(resources_new.len() - 1);
+    }
+
+    resources_new.join(&RESOURCE_DELIMITER.to_string())
+}
+
+/// Classifies a single path segment using caller-supplied rules, falling
+/// back to the built-in classification when no rules are supplied
+fn classify_resource(resource: &str, rules: &[(Regex, String)]) -> String {
+    if !rules.is_empty() {
+        return rules
+            .iter()
+            .find(|(pattern, _)| pattern.is_match(resource))
+            .map(|(_, replacement)| replacement.clone())
+            .unwrap_or_else(|| resource.to_string());
+    }
+
+    lazy_static! {
         static ref UUID: Regex = RegexBuilder::new(
             r#"^[0-9A-F]{8}\-[0-9A-F]{4}\-[0-9A-F]{4}\-[0-9A-F]{4}\-[0-9A-F]{12}$"#
         )
@@ -539,40 +1027,315 @@ where
         static ref CONTAINS_DIGIT: Regex = Regex::new(r#"^.*[0-9]+.*$"#).unwrap();
     }
 
-    let s: &str = Deserialize::deserialize(deserializer)?;
-    if let Some(captures) = REQUEST.captures(s) {
-        if captures.len() < 3 {
-            return Ok(RequestPath::default());
+    if resource.parse::<u32>().is_ok() {
+        INT_PARAM.to_string()
+    } else if UUID.is_match(resource) {
+        UUID_PARAM.to_string()
+    } else if CONTAINS_DIGIT.is_match(resource) {
+        CONTAINS_DIGIT_PARAM.to_string()
+    } else {
+        resource.to_string()
+    }
+}
+
+/// Trend metrics supported by `parse_nginx_log_trends`
+const TREND_METRICS: [&str; 3] = ["count", "errors", "avg_duration"];
+
+/// A path/method series' largest hour-over-hour increase in `metric`
+#[derive(Debug)]
+pub struct EndpointTrend {
+    /// Full request path
+    pub path: String,
+    /// HTTP request method
+    pub http_method: Option<String>,
+    /// Metric value in the hour before the jump (0 if the series is absent)
+    pub before: f64,
+    /// Metric value in the hour of the jump
+    pub after: f64,
+    /// `after - before`
+    pub delta: f64,
+    /// Hour the jump landed in
+    pub hour: String,
+}
+
+/// Parses `path` and reports the `top_n` path/method series with the largest
+/// positive hour-over-hour increase in `metric` (one of `"count"`,
+/// `"errors"`, or `"avg_duration"`), merging over the per-hour maps produced
+/// by a single parse rather than re-parsing per hour. A path missing from a
+/// given hour is treated as zero for that hour.
+pub fn parse_nginx_log_trends(
+    path: &str,
+    metric: &str,
+    top_n: usize,
+) -> Result<Vec<EndpointTrend>, Box<dyn Error>> {
+    parse_nginx_log_trends_with_options(path, metric, top_n, &[], DEFAULT_MAX_PATH_DEPTH)
+}
+
+/// Like `parse_nginx_log_trends`, but lets the caller override path-segment
+/// normalization with `rules` and the maximum path depth retained in the
+/// hierarchy, same as `parse_nginx_log_with_options`
+pub fn parse_nginx_log_trends_with_options(
+    path: &str,
+    metric: &str,
+    top_n: usize,
+    rules: &[(Regex, String)],
+    max_path_depth: usize,
+) -> Result<Vec<EndpointTrend>, Box<dyn Error>> {
+    if !TREND_METRICS.contains(&metric) {
+        return Err(format!(
+            "unknown trend metric {:?}, expected one of {:?}",
+            metric, TREND_METRICS
+        )
+        .into());
+    }
+
+    let statistics = parse_nginx_log_with_options(path, rules, max_path_depth)?;
+    Ok(trending_endpoints(&statistics, metric, top_n))
+}
+
+/// Extracts the chosen trend metric's value from a statistic row
+fn metric_value(statistic: &ServerStatistic, metric: &str) -> f64 {
+    match metric {
+        "errors" => statistic.errors as f64,
+        "avg_duration" => {
+            if statistic.count == 0 {
+                0.0
+            } else {
+                statistic.total_duration as f64 / statistic.count as f64
+            }
         }
+        _ => statistic.count as f64,
+    }
+}
 
-        if let Ok(http_method) = captures[1].parse::<HttpMethod>() {
-            let path = captures[2].to_string();
-
-            let resources: Vec<_> = path.split(RESOURCE_DELIMITER).collect();
-            let mut resources_new = Vec::with_capacity(resources.len());
-
-            for mut resource in resources {
-                if resource.parse::<u32>().is_ok() {
-                    resource = INT_PARAM;
-                } else if UUID.is_match(resource) {
-                    resource = UUID_PARAM;
-                } else if CONTAINS_DIGIT.is_match(resource) {
-                    resource = CONTAINS_DIGIT_PARAM;
-                }
+/// Groups `statistics` by `(path, http_method)`, then for each series finds
+/// the largest positive hour-over-hour increase in `metric` across all
+/// observed hours (treating a series' missing hours as zero), returning the
+/// `top_n` series with the largest jumps
+fn trending_endpoints(
+    statistics: &[ServerStatistic],
+    metric: &str,
+    top_n: usize,
+) -> Vec<EndpointTrend> {
+    let mut hour_keys: Vec<&str> = statistics
+        .iter()
+        .map(|s| s.access_timestamp.as_str())
+        .collect();
+    hour_keys.sort_unstable();
+    hour_keys.dedup();
+
+    let mut by_series: HashMap<(String, Option<String>), HashMap<&str, f64>> = HashMap::new();
+
+    for statistic in statistics {
+        by_series
+            .entry((statistic.path.clone(), statistic.http_method.clone()))
+            .or_default()
+            .insert(
+                statistic.access_timestamp.as_str(),
+                metric_value(statistic, metric),
+            );
+    }
 
-                resources_new.push(resource.to_string());
+    let mut trends = Vec::with_capacity(by_series.len());
+
+    for ((path, http_method), values) in by_series {
+        let mut best: Option<EndpointTrend> = None;
+
+        for window in hour_keys.windows(2) {
+            let before = *values.get(window[0]).unwrap_or(&0.0);
+            let after = *values.get(window[1]).unwrap_or(&0.0);
+            let delta = after - before;
+
+            if delta <= 0.0 {
+                continue;
             }
 
-            if resources_new[resources_new.len() - 1].is_empty() {
-                resources_new.remove(resources_new.len() - 1);
+            if best.as_ref().is_none_or(|b| delta > b.delta) {
+                best = Some(EndpointTrend {
+                    path: path.clone(),
+                    http_method: http_method.clone(),
+                    before,
+                    after,
+                    delta,
+                    hour: window[1].to_string(),
+                });
             }
+        }
 
-            return Ok(RequestPath {
-                http_method,
-                path: resources_new.join(&RESOURCE_DELIMITER.to_string()),
-            });
+        if let Some(best) = best {
+            trends.push(best);
         }
     }
 
-    Ok(RequestPath::default())
+    trends.sort_by(|a, b| b.delta.partial_cmp(&a.delta).unwrap());
+    trends.truncate(top_n);
+
+    trends
+}
+
+/// Renders aggregated statistics in the Prometheus / OpenMetrics text
+/// exposition format: one `# HELP`/`# TYPE` header per metric, then one
+/// sample line per `ServerStatistic`
+pub fn to_prometheus(statistics: &[ServerStatistic]) -> String {
+    let labels: Vec<String> = statistics.iter().map(prometheus_labels).collect();
+
+    let mut output = String::new();
+
+    push_prometheus_family(
+        &mut output,
+        "nginx_requests_total",
+        "Total number of requests",
+        "counter",
+        statistics,
+        &labels,
+        |s| s.count as f32,
+    );
+    push_prometheus_family(
+        &mut output,
+        "nginx_bytes_sent_kb_total",
+        "Total amount of data sent, in kilobytes",
+        "counter",
+        statistics,
+        &labels,
+        |s| s.kb_sent,
+    );
+    push_prometheus_family(
+        &mut output,
+        "nginx_request_duration_ms_sum",
+        "Total request duration, in milliseconds",
+        "counter",
+        statistics,
+        &labels,
+        |s| s.total_duration,
+    );
+    push_prometheus_family(
+        &mut output,
+        "nginx_request_duration_ms_min",
+        "Minimum request duration, in milliseconds",
+        "gauge",
+        statistics,
+        &labels,
+        |s| s.min_duration,
+    );
+    push_prometheus_family(
+        &mut output,
+        "nginx_request_duration_ms_max",
+        "Maximum request duration, in milliseconds",
+        "gauge",
+        statistics,
+        &labels,
+        |s| s.max_duration,
+    );
+    push_prometheus_family(
+        &mut output,
+        "nginx_errors_total",
+        "Total number of 5xx errors",
+        "counter",
+        statistics,
+        &labels,
+        |s| s.errors as f32,
+    );
+
+    output
+}
+
+/// Appends one metric family (HELP + TYPE headers, then one sample line per
+/// statistic) to `output`
+fn push_prometheus_family<F>(
+    output: &mut String,
+    name: &str,
+    help: &str,
+    metric_type: &str,
+    statistics: &[ServerStatistic],
+    labels: &[String],
+    value_fn: F,
+) where
+    F: Fn(&ServerStatistic) -> f32,
+{
+    let _ = writeln!(output, "# HELP {} {}", name, help);
+    let _ = writeln!(output, "# TYPE {} {}", name, metric_type);
+
+    for (statistic, label) in statistics.iter().zip(labels) {
+        let _ = writeln!(output, "{}{} {}", name, label, value_fn(statistic));
+    }
+}
+
+/// Builds the `{...}` label block for a statistic, collapsing `None`
+/// `http_method` rows into a series without the `method` label
+fn prometheus_labels(statistic: &ServerStatistic) -> String {
+    let mut labels = vec![format!(
+        "path=\"{}\"",
+        escape_prometheus_label(&statistic.path)
+    )];
+
+    if let Some(http_method) = &statistic.http_method {
+        labels.push(format!(
+            "method=\"{}\"",
+            escape_prometheus_label(http_method)
+        ));
+    }
+
+    labels.push(format!(
+        "hour=\"{}\"",
+        escape_prometheus_label(&statistic.access_timestamp)
+    ));
+
+    format!("{{{}}}", labels.join(","))
+}
+
+/// Escapes a Prometheus/OpenMetrics label value: backslashes, double quotes
+/// and newlines must be escaped per the text exposition format
+fn escape_prometheus_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Builds a `Metrics` whose histogram has `count` requests in each given
+    /// bucket index, with `count` set to match
+    fn metrics_with_bucket_counts(counts: &[(usize, u32)]) -> Metrics {
+        let mut metrics = Metrics::default();
+
+        for &(bucket, count) in counts {
+            metrics.buckets[bucket] = count;
+            metrics.count += count;
+        }
+
+        metrics
+    }
+
+    #[test]
+    fn quantile_returns_boundary_of_bucket_containing_target_rank() {
+        // 100 requests spread across the first four buckets
+        let metrics = metrics_with_bucket_counts(&[(0, 50), (1, 30), (2, 15), (3, 5)]);
+
+        assert_eq!(metrics.quantile(0.50), HISTOGRAM_BOUNDARIES[0]);
+        assert_eq!(metrics.quantile(0.90), HISTOGRAM_BOUNDARIES[2]);
+        assert_eq!(metrics.quantile(0.95), HISTOGRAM_BOUNDARIES[2]);
+        assert_eq!(metrics.quantile(0.99), HISTOGRAM_BOUNDARIES[3]);
+    }
+
+    #[test]
+    fn quantile_with_zero_requests_is_zero() {
+        let metrics = Metrics::default();
+
+        assert_eq!(metrics.quantile(0.50), 0.0);
+        assert_eq!(metrics.quantile(0.99), 0.0);
+    }
+
+    #[test]
+    fn histogram_bucket_picks_first_boundary_strictly_above_duration() {
+        assert_eq!(histogram_bucket(0.0), 0);
+        assert_eq!(histogram_bucket(HISTOGRAM_BOUNDARIES[0]), 1);
+    }
+
+    #[test]
+    fn histogram_bucket_clamps_durations_above_the_top_boundary() {
+        assert_eq!(histogram_bucket(f32::MAX), HISTOGRAM_BUCKET_COUNT - 1);
+    }
 }
@@ -1,9 +1,19 @@
 // Scripts
 
+use std::cell::RefCell;
+use std::time::Duration;
+
 use logic::utils::scripts;
-use logic::utils::scripts::parse_nginx_log::ServerStatistic;
+use logic::utils::scripts::parse_nginx_log::{
+    EndpointTrend, NginxLogFollower, ServerStatistic, DEFAULT_MAX_PATH_DEPTH,
+    DEFAULT_POLL_INTERVAL_MS,
+};
 
-use cpython::{py_fn, PyDict, PyList, PyModule, PyResult, Python, PythonObject};
+use cpython::{
+    exc, py_class, py_fn, PyClone, PyDict, PyErr, PyList, PyModule, PyResult, PyString, Python,
+    PythonObject,
+};
+use regex::Regex;
 
 // Python Module definition
 pub fn module(py: Python<'_>) -> PyResult<PyModule> {
@@ -13,7 +23,71 @@ pub fn module(py: Python<'_>) -> PyResult<PyModule> {
     scripts.add(
         py,
         "parse_nginx_log",
-        py_fn!(py, parse_nginx_log(path: &str)),
+        py_fn!(
+            py,
+            parse_nginx_log(
+                path: &str,
+                rules: Option<Vec<(String, String)>> = None,
+                max_path_depth: usize = DEFAULT_MAX_PATH_DEPTH
+            )
+        ),
+    )?;
+
+    scripts.add(
+        py,
+        "parse_nginx_log_prometheus",
+        py_fn!(
+            py,
+            parse_nginx_log_prometheus(
+                path: &str,
+                rules: Option<Vec<(String, String)>> = None,
+                max_path_depth: usize = DEFAULT_MAX_PATH_DEPTH
+            )
+        ),
+    )?;
+
+    scripts.add(
+        py,
+        "parse_nginx_log_resume",
+        py_fn!(
+            py,
+            parse_nginx_log_resume(
+                path: &str,
+                state_path: &str,
+                rules: Option<Vec<(String, String)>> = None,
+                max_path_depth: usize = DEFAULT_MAX_PATH_DEPTH
+            )
+        ),
+    )?;
+
+    scripts.add(
+        py,
+        "parse_nginx_log_follow",
+        py_fn!(
+            py,
+            parse_nginx_log_follow(
+                path: &str,
+                follow: bool = false,
+                rules: Option<Vec<(String, String)>> = None,
+                max_path_depth: usize = DEFAULT_MAX_PATH_DEPTH,
+                poll_interval_ms: u64 = DEFAULT_POLL_INTERVAL_MS
+            )
+        ),
+    )?;
+
+    scripts.add(
+        py,
+        "parse_nginx_log_trends",
+        py_fn!(
+            py,
+            parse_nginx_log_trends(
+                path: &str,
+                metric: &str,
+                top_n: usize,
+                rules: Option<Vec<(String, String)>> = None,
+                max_path_depth: usize = DEFAULT_MAX_PATH_DEPTH
+            )
+        ),
     )?;
 
     Ok(scripts)
@@ -36,21 +110,198 @@ fn server_statistic_to_dict(py: Python<'_>, statistic: ServerStatistic) -> PyRes
     dict.set_item(py, "min_duration", statistic.min_duration)?;
     dict.set_item(py, "max_duration", statistic.max_duration)?;
     dict.set_item(py, "errors", statistic.errors)?;
+    dict.set_item(py, "p50", statistic.p50)?;
+    dict.set_item(py, "p90", statistic.p90)?;
+    dict.set_item(py, "p95", statistic.p95)?;
+    dict.set_item(py, "p99", statistic.p99)?;
 
     Ok(dict)
 }
 
-fn parse_nginx_log(py: Python<'_>, path: &str) -> PyResult<PyList> {
-    if let Ok(results) = scripts::parse_nginx_log::parse_nginx_log(path) {
-        let list = PyList::new(py, &[]);
+fn server_statistics_to_pylist(
+    py: Python<'_>,
+    statistics: Vec<ServerStatistic>,
+) -> PyResult<PyList> {
+    let list = PyList::new(py, &[]);
 
-        for (i, result) in results.into_iter().enumerate() {
-            let dict = server_statistic_to_dict(py, result)?;
-            list.insert(py, i, dict.into_object());
-        }
+    for (i, result) in statistics.into_iter().enumerate() {
+        let dict = server_statistic_to_dict(py, result)?;
+        list.insert(py, i, dict.into_object());
+    }
+
+    Ok(list)
+}
+
+fn compile_path_rules(
+    rules: Option<Vec<(String, String)>>,
+) -> Result<Vec<(Regex, String)>, regex::Error> {
+    match rules {
+        Some(rules) => rules
+            .into_iter()
+            .map(|(pattern, replacement)| Regex::new(&pattern).map(|regex| (regex, replacement)))
+            .collect(),
+        None => Ok(Vec::new()),
+    }
+}
+
+fn parse_nginx_log(
+    py: Python<'_>,
+    path: &str,
+    rules: Option<Vec<(String, String)>>,
+    max_path_depth: usize,
+) -> PyResult<PyList> {
+    let rules = match compile_path_rules(rules) {
+        Ok(rules) => rules,
+        Err(_) => return Ok(PyList::new(py, &[])),
+    };
 
-        Ok(list)
+    if let Ok(results) =
+        scripts::parse_nginx_log::parse_nginx_log_with_options(path, &rules, max_path_depth)
+    {
+        server_statistics_to_pylist(py, results)
     } else {
         Ok(PyList::new(py, &[]))
     }
 }
+
+fn parse_nginx_log_prometheus(
+    py: Python<'_>,
+    path: &str,
+    rules: Option<Vec<(String, String)>>,
+    max_path_depth: usize,
+) -> PyResult<PyString> {
+    let rules = match compile_path_rules(rules) {
+        Ok(rules) => rules,
+        Err(_) => return Ok(PyString::new(py, "")),
+    };
+
+    match scripts::parse_nginx_log::parse_nginx_log_prometheus_with_options(
+        path,
+        &rules,
+        max_path_depth,
+    ) {
+        Ok(output) => Ok(PyString::new(py, &output)),
+        Err(_) => Ok(PyString::new(py, "")),
+    }
+}
+
+fn parse_nginx_log_resume(
+    py: Python<'_>,
+    path: &str,
+    state_path: &str,
+    rules: Option<Vec<(String, String)>>,
+    max_path_depth: usize,
+) -> PyResult<PyList> {
+    let rules = match compile_path_rules(rules) {
+        Ok(rules) => rules,
+        Err(_) => return Ok(PyList::new(py, &[])),
+    };
+
+    if let Ok(results) = scripts::parse_nginx_log::parse_nginx_log_resume_with_options(
+        path,
+        state_path,
+        &rules,
+        max_path_depth,
+    ) {
+        server_statistics_to_pylist(py, results)
+    } else {
+        Ok(PyList::new(py, &[]))
+    }
+}
+
+fn endpoint_trend_to_dict(py: Python<'_>, trend: EndpointTrend) -> PyResult<PyDict> {
+    let dict = PyDict::new(py);
+
+    dict.set_item(py, "path", trend.path)?;
+
+    match &trend.http_method {
+        Some(http_method) => dict.set_item(py, "http_method", http_method)?,
+        None => dict.set_item(py, "http_method", py.None())?,
+    }
+
+    dict.set_item(py, "before", trend.before)?;
+    dict.set_item(py, "after", trend.after)?;
+    dict.set_item(py, "delta", trend.delta)?;
+    dict.set_item(py, "hour", trend.hour)?;
+
+    Ok(dict)
+}
+
+fn parse_nginx_log_trends(
+    py: Python<'_>,
+    path: &str,
+    metric: &str,
+    top_n: usize,
+    rules: Option<Vec<(String, String)>>,
+    max_path_depth: usize,
+) -> PyResult<PyList> {
+    let rules =
+        compile_path_rules(rules).map_err(|e| PyErr::new::<exc::IOError, _>(py, e.to_string()))?;
+
+    match scripts::parse_nginx_log::parse_nginx_log_trends_with_options(
+        path,
+        metric,
+        top_n,
+        &rules,
+        max_path_depth,
+    ) {
+        Ok(trends) => {
+            let list = PyList::new(py, &[]);
+
+            for (i, trend) in trends.into_iter().enumerate() {
+                let dict = endpoint_trend_to_dict(py, trend)?;
+                list.insert(py, i, dict.into_object());
+            }
+
+            Ok(list)
+        }
+        Err(e) => Err(PyErr::new::<exc::IOError, _>(py, format!("{:?}", e))),
+    }
+}
+
+fn parse_nginx_log_follow(
+    py: Python<'_>,
+    path: &str,
+    follow: bool,
+    rules: Option<Vec<(String, String)>>,
+    max_path_depth: usize,
+    poll_interval_ms: u64,
+) -> PyResult<NginxLogFollowerIter> {
+    let rules =
+        compile_path_rules(rules).map_err(|e| PyErr::new::<exc::IOError, _>(py, e.to_string()))?;
+
+    let follower = NginxLogFollower::with_options(
+        path,
+        follow,
+        Duration::from_millis(poll_interval_ms),
+        &rules,
+        max_path_depth,
+    )
+    .map_err(|e| PyErr::new::<exc::IOError, _>(py, format!("{:?}", e)))?;
+
+    NginxLogFollowerIter::create_instance(py, RefCell::new(follower))
+}
+
+py_class!(class NginxLogFollowerIter |py| {
+    data follower: RefCell<NginxLogFollower>;
+
+    def __iter__(&self) -> PyResult<NginxLogFollowerIter> {
+        Ok(self.clone_ref(py))
+    }
+
+    def __next__(&self) -> PyResult<Option<PyList>> {
+        let mut follower = self.follower(py).borrow_mut();
+        let follower: &mut NginxLogFollower = &mut follower;
+
+        // the poll/sleep loop inside `next_sealed_hour` can block for a
+        // while in follow mode; release the GIL so other Python threads
+        // (and signal delivery) aren't frozen for the duration
+        let result = py.allow_threads(|| follower.next_sealed_hour());
+
+        match result {
+            Ok(Some(statistics)) => Ok(Some(server_statistics_to_pylist(py, statistics)?)),
+            Ok(None) => Ok(None),
+            Err(e) => Err(PyErr::new::<exc::IOError, _>(py, format!("{:?}", e))),
+        }
+    }
+});